@@ -19,6 +19,8 @@ use crate::utils::ThreadIdPair;
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub telegram: Telegram,
+    #[serde(default)]
+    pub matrix: Option<Matrix>,
     pub server_addr: SocketAddr,
     pub services: Services,
     #[serde(default)]
@@ -27,6 +29,18 @@ pub struct Config {
     pub borrowed_items: BorrowedItemsConfig,
 }
 
+/// Credentials and room allow-list for the optional Matrix front-end (see
+/// `crate::matrix`). `allowed_rooms` is a list of Matrix room ids; an
+/// empty list means every room the bot's account is joined to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Matrix {
+    pub homeserver_url: String,
+    pub user: String,
+    pub access_token: String,
+    #[serde(default)]
+    pub allowed_rooms: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Telegram {
     pub token: String,
@@ -0,0 +1,167 @@
+//! Matrix front-end: a second [`Transport`](crate::transport::Transport)
+//! implementation, alongside Telegram, so people on the space's Matrix
+//! homeserver get `/status`, `/topics`, and `/help` too.
+//!
+//! One command implementation (`modules::basic::cmd_*_impl`) is shared
+//! between both front-ends; only login, the sync loop, and the room
+//! message -> command mapping are Matrix-specific. `/residents` is
+//! intentionally not offered here: it's resident-gated on Telegram, and
+//! Matrix identities aren't linked to residents yet, so that gate can't be
+//! enforced. Every Matrix sender is therefore reported to the shared
+//! commands as having no resident identity at all (`IncomingMessage::
+//! user_id: None`), the same as an anonymous caller.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use matrix_sdk::attachment::AttachmentConfig;
+use matrix_sdk::config::SyncSettings;
+use matrix_sdk::room::Room;
+use matrix_sdk::ruma::events::room::message::{
+    MessageType, OriginalSyncRoomMessageEvent, RoomMessageEventContent,
+};
+use matrix_sdk::{matrix_auth, Client, SessionMeta};
+use tokio::sync::RwLock;
+
+use crate::common::BotEnv;
+use crate::config::Matrix as MatrixConfig;
+use crate::db::DbChatId;
+use crate::modules::basic::{cmd_help_impl, cmd_status_impl, cmd_topics_impl};
+use crate::modules::mac_monitoring::State;
+use crate::transport::{IncomingMessage, Transport};
+use crate::utils::mikrotik::SharedMikrotikCache;
+
+/// Replies into a specific Matrix room.
+struct MatrixTransport<'a> {
+    room: &'a Room,
+}
+
+#[async_trait]
+impl Transport for MatrixTransport<'_> {
+    async fn reply_html(&self, _incoming: &IncomingMessage, html: &str) -> Result<()> {
+        let plain = strip_tags(html);
+        self.room.send(RoomMessageEventContent::text_html(plain, html)).await?;
+        Ok(())
+    }
+
+    async fn reply_photo(&self, _incoming: &IncomingMessage, png_bytes: Vec<u8>) -> Result<()> {
+        self.room
+            .send_attachment("image.png", &mime::IMAGE_PNG, png_bytes, AttachmentConfig::new())
+            .await?;
+        Ok(())
+    }
+
+    async fn send_chat_action(&self, _incoming: &IncomingMessage) -> Result<()> {
+        self.room.typing_notice(true).await?;
+        Ok(())
+    }
+}
+
+/// Strips the small HTML subset the commands produce (`<b>`), for clients
+/// that ignore `formatted_body`.
+fn strip_tags(html: &str) -> String {
+    html.replace("<b>", "").replace("</b>", "")
+}
+
+/// Derives a `DbChatId` from a Matrix room id, so the shared commands
+/// don't need a Matrix-specific chat identity type.
+fn room_db_chat_id(room: &Room) -> DbChatId {
+    let mut hasher = DefaultHasher::new();
+    room.room_id().as_str().hash(&mut hasher);
+    DbChatId::from(hasher.finish() as i64)
+}
+
+/// Logs in with a pre-issued access token, runs the sync loop, and
+/// dispatches `/status`, `/topics`, and `/help` in whichever rooms
+/// `matrix.allowed_rooms` permits (all joined rooms if empty).
+pub async fn run(
+    conf: MatrixConfig,
+    env: Arc<BotEnv>,
+    mac_monitoring_state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
+) -> Result<()> {
+    let user_id = conf.user.parse().context("invalid Matrix user id")?;
+    let client = Client::builder()
+        .homeserver_url(&conf.homeserver_url)
+        .build()
+        .await
+        .context("failed to build the Matrix client")?;
+    client
+        .restore_session(matrix_auth::MatrixSession {
+            meta: SessionMeta { user_id, device_id: "botka".into() },
+            tokens: matrix_auth::MatrixSessionTokens {
+                access_token: conf.access_token.clone(),
+                refresh_token: None,
+            },
+        })
+        .await
+        .context("failed to restore the Matrix session")?;
+
+    let allowed_rooms = conf.allowed_rooms.clone();
+    client.add_event_handler({
+        let env = Arc::clone(&env);
+        let mac_monitoring_state = Arc::clone(&mac_monitoring_state);
+        let mikrotik_cache = Arc::clone(&mikrotik_cache);
+        move |ev: OriginalSyncRoomMessageEvent, room: Room| {
+            let env = Arc::clone(&env);
+            let mac_monitoring_state = Arc::clone(&mac_monitoring_state);
+            let mikrotik_cache = Arc::clone(&mikrotik_cache);
+            let allowed_rooms = allowed_rooms.clone();
+            async move {
+                if !allowed_rooms.is_empty()
+                    && !allowed_rooms.iter().any(|r| r == room.room_id().as_str())
+                {
+                    return;
+                }
+                if let Err(e) =
+                    handle_message(ev, room, env, mac_monitoring_state, mikrotik_cache).await
+                {
+                    log::error!("matrix: failed to handle room message: {e}");
+                }
+            }
+        }
+    });
+
+    log::info!("matrix: starting sync loop against {}", conf.homeserver_url);
+    client.sync(SyncSettings::default()).await?;
+    Ok(())
+}
+
+async fn handle_message(
+    ev: OriginalSyncRoomMessageEvent,
+    room: Room,
+    env: Arc<BotEnv>,
+    mac_monitoring_state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
+) -> Result<()> {
+    let MessageType::Text(text) = ev.content.msgtype else { return Ok(()) };
+    let Some(command) = text.body.strip_prefix('/') else { return Ok(()) };
+    let command_name = command.split_whitespace().next().unwrap_or_default();
+
+    let transport = MatrixTransport { room: &room };
+    let incoming = IncomingMessage {
+        chat_id: room_db_chat_id(&room),
+        // Matrix identities aren't linked to residents, so there's no
+        // `DbUserId` to report; `cmd_topics_impl` treats `None` as "no
+        // resident mapping" and falls back to every tracked topic.
+        user_id: None,
+    };
+
+    match command_name {
+        "status" => {
+            cmd_status_impl(&transport, &env, &mac_monitoring_state, &mikrotik_cache, incoming)
+                .await
+        }
+        "topics" => cmd_topics_impl(&transport, &env, incoming).await,
+        "help" => cmd_help_impl(&transport, &env, incoming).await,
+        // `Commands::Residents` is resident-gated on Telegram
+        // (`#[custom(resident = true)]`), but Matrix identities aren't
+        // linked to residents yet (`user_id` is always `None` above), so there's no
+        // way to enforce that gate here. Leave it unavailable over Matrix
+        // rather than let any joined-room user dump the resident list.
+        _ => Ok(()),
+    }
+}
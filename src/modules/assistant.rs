@@ -0,0 +1,324 @@
+//! LLM tool-calling assistant that answers natural-language questions
+//! about the bot's own data (residents, presence, topics) by letting the
+//! model drive the same Diesel queries the structured commands use.
+//!
+//! This is a message handler, not a `BotCommands` variant: it reacts to
+//! `nlp.trigger_words`, an occasional random jump-in (`nlp.
+//! random_answer_probability`), or a message the cheap `classification_
+//! model` decides is actually a question for the bot.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use teloxide::prelude::*;
+use teloxide::utils::html;
+use tokio::sync::RwLock;
+
+use super::basic::cmd_status_text;
+use super::mac_monitoring::State;
+use crate::common::BotEnv;
+use crate::transport::Transport;
+use crate::utils::mikrotik::SharedMikrotikCache;
+use crate::{models, schema};
+
+/// How many tool-call round-trips a single question may take before we
+/// give up and reply with a fixed "couldn't find an answer" message.
+const MAX_TOOL_ROUNDS: u32 = 4;
+
+/// If the trigger-word/classification checks don't fire, reacts anyway
+/// with this (percent) probability, so the assistant occasionally jumps
+/// into conversation unprompted.
+fn random_answer_roll(probability_percent: f64) -> bool {
+    rand::thread_rng().gen_range(0.0..100.0) < probability_percent
+}
+
+/// Entry point wired into `basic::command_handler` via `dptree::
+/// filter_map_async`: decides whether to answer at all, and if so, runs
+/// the tool-calling loop and replies. Returns `Some(())` once it has
+/// replied, or `None` to fall through to sibling handlers — the same
+/// "claim it or let it fall through" contract `macros::resolve_macro_
+/// command` uses for the branch above it.
+pub async fn maybe_answer(
+    bot: Bot,
+    env: Arc<BotEnv>,
+    msg: Message,
+    mac_monitoring_state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
+) -> Option<()> {
+    match try_answer(&bot, &env, &msg, &mac_monitoring_state, &mikrotik_cache).await {
+        Ok(true) => Some(()),
+        Ok(false) => None,
+        Err(e) => {
+            log::error!("assistant: failed to answer {:?}: {e}", msg.id);
+            None
+        }
+    }
+}
+
+async fn try_answer(
+    bot: &Bot,
+    env: &Arc<BotEnv>,
+    msg: &Message,
+    mac_monitoring_state: &Arc<RwLock<State>>,
+    mikrotik_cache: &SharedMikrotikCache,
+) -> Result<bool> {
+    let nlp = &env.config.nlp;
+    if !nlp.enabled {
+        return Ok(false);
+    }
+    let Some(text) = msg.text() else { return Ok(false) };
+    let text_lower = text.to_lowercase();
+
+    let directly_addressed =
+        nlp.trigger_words.iter().any(|w| text_lower.contains(&w.to_lowercase()));
+    let should_answer = if directly_addressed {
+        true
+    } else if is_question_for_bot(env, text).await? {
+        true
+    } else {
+        random_answer_roll(nlp.random_answer_probability)
+    };
+    if !should_answer {
+        return Ok(false);
+    }
+
+    let answer = answer_question(env, mac_monitoring_state, mikrotik_cache, text).await?;
+    let transport = super::basic::TelegramTransport { bot, msg };
+    transport.reply_html(&transport.incoming(), &html::escape(&answer)).await?;
+    Ok(true)
+}
+
+/// Uses `NlpConfig::classification_model` (cheap/fast) to decide whether
+/// `text` is actually a question addressed to the bot, so we only fall
+/// back to `random_answer_probability` for ordinary chatter.
+async fn is_question_for_bot(env: &Arc<BotEnv>, text: &str) -> Result<bool> {
+    let Some(model) = &env.config.nlp.classification_model else {
+        return Ok(false);
+    };
+
+    let messages = json!([
+        {
+            "role": "system",
+            "content": "Answer with exactly one word, \"yes\" or \"no\": is the following \
+                 message a question directed at this hackerspace's bot?",
+        },
+        {"role": "user", "content": text},
+    ]);
+    let response = chat_completion(env, model, messages, None).await?;
+    let answer = response
+        .choices
+        .first()
+        .and_then(|c| c.message.content.as_deref())
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    Ok(answer.starts_with("yes"))
+}
+
+/// Runs the tool-calling loop against `nlp.models[0]` (the cheapest
+/// configured model) and returns the model's final answer.
+async fn answer_question(
+    env: &Arc<BotEnv>,
+    mac_monitoring_state: &Arc<RwLock<State>>,
+    mikrotik_cache: &SharedMikrotikCache,
+    question: &str,
+) -> Result<String> {
+    let model = env
+        .config
+        .nlp
+        .models
+        .first()
+        .context("nlp.models is empty")?
+        .clone();
+
+    let mut messages = vec![json!({
+        "role": "system",
+        "content": "You are the assistant for a hackerspace's Telegram bot. Answer \
+             questions about residents, who is currently in the space, and the chat \
+             topic list by calling the provided tools; never guess at data you can \
+             look up.",
+    })];
+    messages.push(json!({"role": "user", "content": question}));
+
+    for _round in 0..MAX_TOOL_ROUNDS {
+        let response = chat_completion(env, &model, json!(messages), Some(tool_schema())).await?;
+        let Some(choice) = response.choices.into_iter().next() else {
+            break;
+        };
+
+        if choice.message.tool_calls.is_empty() {
+            return Ok(choice.message.content.unwrap_or_default());
+        }
+
+        messages.push(json!({
+            "role": "assistant",
+            "content": choice.message.content,
+            "tool_calls": choice.message.tool_calls,
+        }));
+        for call in &choice.message.tool_calls {
+            let result = run_tool(env, mac_monitoring_state, mikrotik_cache, &call.function.name)
+                .await
+                .unwrap_or_else(|e| json!({"error": e.to_string()}));
+            messages.push(json!({
+                "role": "tool",
+                "tool_call_id": call.id,
+                "content": result.to_string(),
+            }));
+        }
+    }
+
+    Ok("Sorry, I couldn't find an answer in time.".to_string())
+}
+
+async fn run_tool(
+    env: &Arc<BotEnv>,
+    mac_monitoring_state: &Arc<RwLock<State>>,
+    mikrotik_cache: &SharedMikrotikCache,
+    name: &str,
+) -> Result<Value> {
+    match name {
+        "list_residents" => Ok(json!({ "residents": list_residents(env)? })),
+        "who_is_here" => {
+            let text = cmd_status_text(env, mac_monitoring_state, mikrotik_cache).await?;
+            Ok(json!({ "status": text }))
+        }
+        "list_topics" => Ok(json!({ "topics": list_all_topics(env)? })),
+        other => Ok(json!({ "error": format!("unknown tool {other:?}") })),
+    }
+}
+
+fn list_residents(env: &Arc<BotEnv>) -> Result<Vec<String>> {
+    let residents: Vec<Option<models::TgUser>> = schema::residents::table
+        .filter(schema::residents::end_date.is_null())
+        .left_join(
+            schema::tg_users::table.on(schema::residents::tg_id.eq(schema::tg_users::id)),
+        )
+        .select(schema::tg_users::all_columns.nullable())
+        .order(schema::residents::begin_date.desc())
+        .load(&mut *env.conn())?;
+    Ok(residents
+        .into_iter()
+        .map(|u| u.map_or_else(|| "(unknown)".to_string(), |u| u.username.unwrap_or(u.first_name)))
+        .collect())
+}
+
+/// A flat `"<chat title>: <topic name>"` list across every tracked chat,
+/// the same data `cmd_topics` renders per-chat as a bulleted list.
+fn list_all_topics(env: &Arc<BotEnv>) -> Result<Vec<String>> {
+    let topics: Vec<(Option<String>, models::TgChatTopic)> = schema::tg_chat_topics::table
+        .inner_join(schema::tg_chats::table.on(schema::tg_chat_topics::chat_id.eq(schema::tg_chats::id)))
+        .select((schema::tg_chats::title, schema::tg_chat_topics::all_columns))
+        .load(&mut *env.conn())?;
+    Ok(topics
+        .into_iter()
+        .map(|(chat_title, topic)| {
+            format!(
+                "{}: {}",
+                chat_title.as_deref().unwrap_or("(unnamed chat)"),
+                topic.name.as_deref().unwrap_or("(unnamed topic)")
+            )
+        })
+        .collect())
+}
+
+fn tool_schema() -> Value {
+    json!([
+        {
+            "type": "function",
+            "function": {
+                "name": "list_residents",
+                "description": "List the current residents of the hackerspace.",
+                "parameters": {"type": "object", "properties": {}},
+            },
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "who_is_here",
+                "description": "List who is currently present in the space, based on Mikrotik presence tracking.",
+                "parameters": {"type": "object", "properties": {}},
+            },
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "list_topics",
+                "description": "List the active chat topics across all tracked chats.",
+                "parameters": {"type": "object", "properties": {}},
+            },
+        },
+    ])
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatCompletionMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionMessage {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Deserialize, Clone, serde::Serialize)]
+struct ToolCall {
+    id: String,
+    /// Always `"function"` for chat-completions tool calls; kept as a real
+    /// field (rather than hardcoded on replay) so re-serializing `messages`
+    /// for the next round includes it, which OpenAI-compatible APIs require.
+    #[serde(rename = "type", default = "tool_call_type")]
+    kind: String,
+    function: ToolCallFunction,
+}
+
+fn tool_call_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone, serde::Serialize)]
+struct ToolCallFunction {
+    name: String,
+    #[serde(default)]
+    arguments: String,
+}
+
+async fn chat_completion(
+    env: &Arc<BotEnv>,
+    model: &str,
+    messages: Value,
+    tools: Option<Value>,
+) -> Result<ChatCompletionResponse> {
+    let openai = &env.config.services.openai;
+    let api_base = openai
+        .api_base
+        .as_deref()
+        .unwrap_or("https://openrouter.ai/api/v1");
+
+    let mut body = json!({"model": model, "messages": messages});
+    if let Some(tools) = tools {
+        body["tools"] = tools;
+    }
+
+    let response = env
+        .reqwest_client
+        .post(format!("{api_base}/chat/completions"))
+        .bearer_auth(&openai.api_key)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<ChatCompletionResponse>()
+        .await?;
+    Ok(response)
+}
@@ -9,22 +9,24 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use diesel::prelude::*;
-use itertools::Itertools;
 use macro_rules_attribute::derive;
+use teloxide::dptree;
 use teloxide::prelude::*;
-use teloxide::types::{InputFile, ThreadId};
+use teloxide::types::InputFile;
 use teloxide::utils::command::BotCommands;
 use teloxide::utils::html;
 use tokio::sync::RwLock;
 
 use super::mac_monitoring::State;
+use super::occupancy::cmd_occupancy;
+use super::reminders::cmd_remind;
 use crate::common::{
-    filter_command, format_users, BotCommandsExt, BotCommandsExtTrait, BotEnv,
-    TopicEmojis, UpdateHandler,
+    filter_command, format_users, BotCommandsExt, BotCommandsExtTrait, BotEnv, UpdateHandler,
 };
 use crate::db::{DbChatId, DbUserId};
-use crate::utils::{write_message_link, BotExt};
-use crate::utils::mikrotik::get_leases;
+use crate::transport::{IncomingMessage, Transport};
+use crate::utils::BotExt;
+use crate::utils::mikrotik::SharedMikrotikCache;
 use crate::{models, schema};
 
 #[derive(Clone, BotCommands, BotCommandsExt!)]
@@ -45,6 +47,20 @@ pub enum Commands {
     #[custom(resident = true)]
     ResidentsTimeline,
 
+    #[command(description = "show weekly occupancy heatmap.")]
+    Occupancy,
+
+    #[command(
+        description = "set a reminder, e.g. `2h30m water the plants` or `every tuesday 07:00 clean the lab`."
+    )]
+    Remind(String),
+
+    #[command(
+        description = "manage command macros, e.g. `/macro add here status` or `/macro remove here`."
+    )]
+    #[custom(resident = true)]
+    Macro(String),
+
     #[command(description = "show status.")]
     Status,
 
@@ -57,7 +73,19 @@ pub enum Commands {
 }
 
 pub fn command_handler() -> UpdateHandler {
-    filter_command::<Commands>().endpoint(start)
+    dptree::entry()
+        .branch(filter_command::<Commands>().endpoint(start))
+        .branch(
+            dptree::filter_map_async(super::macros::resolve_macro_command)
+                .endpoint(start_from_macro),
+        )
+        .branch(dptree::filter_map_async(super::assistant::maybe_answer).endpoint(noop))
+}
+
+/// `maybe_answer` already replies (or decides not to) by itself; this just
+/// satisfies the endpoint the `filter_map_async` branch above requires.
+async fn noop() -> Result<()> {
+    Ok(())
 }
 
 async fn start(
@@ -65,10 +93,35 @@ async fn start(
     env: Arc<BotEnv>,
     msg: Message,
     mac_monitoring_state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
+    command: Commands,
+) -> Result<()> {
+    dispatch(bot, env, msg, mac_monitoring_state, mikrotik_cache, command).await
+}
+
+async fn start_from_macro(
+    bot: Bot,
+    env: Arc<BotEnv>,
+    msg: Message,
+    mac_monitoring_state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
+    command: Commands,
+) -> Result<()> {
+    dispatch(bot, env, msg, mac_monitoring_state, mikrotik_cache, command).await
+}
+
+/// Runs a resolved [`Commands`] invocation, whether it came straight off
+/// the wire or was expanded from a chat macro by [`start_from_macro`].
+pub(crate) async fn dispatch(
+    bot: Bot,
+    env: Arc<BotEnv>,
+    msg: Message,
+    mac_monitoring_state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
     command: Commands,
 ) -> Result<()> {
     match command {
-        Commands::Help => cmd_help(bot, msg).await?,
+        Commands::Help => cmd_help(bot, env, msg).await?,
         Commands::Residents => cmd_list_residents(bot, env, msg).await?,
         Commands::ResidentsAdminTable => {
             cmd_residents_admin_table(bot, env, msg).await?;
@@ -76,8 +129,11 @@ async fn start(
         Commands::ResidentsTimeline => {
             cmd_show_residents_timeline(bot, msg).await?;
         }
+        Commands::Remind(text) => cmd_remind(bot, env, msg, text).await?,
+        Commands::Macro(args) => super::macros::cmd_macro(bot, env, msg, args).await?,
+        Commands::Occupancy => cmd_occupancy(bot, msg).await?,
         Commands::Status => {
-            cmd_status(bot, env, msg, mac_monitoring_state).await?;
+            cmd_status(bot, env, msg, mac_monitoring_state, mikrotik_cache).await?;
         }
         Commands::Version => {
             bot.reply_message(&msg, crate::version()).await?;
@@ -87,7 +143,86 @@ async fn start(
     Ok(())
 }
 
-async fn cmd_help(bot: Bot, msg: Message) -> Result<()> {
+/// Wraps a live Telegram `Bot`/`Message` pair as a [`Transport`], so the
+/// shared read commands below have exactly one implementation. `pub(crate)`
+/// so other modules with their own non-command reply paths (e.g.
+/// `assistant::try_answer`) get the same chunking/parse-mode handling
+/// instead of rolling their own.
+pub(crate) struct TelegramTransport<'a> {
+    pub(crate) bot: &'a Bot,
+    pub(crate) msg: &'a Message,
+}
+
+impl TelegramTransport<'_> {
+    pub(crate) fn incoming(&self) -> IncomingMessage {
+        IncomingMessage {
+            chat_id: DbChatId::from(self.msg.chat.id),
+            user_id: self.msg.from.as_ref().map(|u| DbUserId::from(u.id)),
+        }
+    }
+}
+
+/// Telegram's practical message length cap (its hard limit is 4096 UTF-16
+/// code units; stay comfortably under it).
+const TELEGRAM_MESSAGE_LIMIT: usize = 4000;
+
+#[async_trait::async_trait]
+impl Transport for TelegramTransport<'_> {
+    async fn reply_html(&self, _incoming: &IncomingMessage, html: &str) -> Result<()> {
+        for chunk in chunk_by_lines(html, TELEGRAM_MESSAGE_LIMIT) {
+            self.bot
+                .reply_message(self.msg, chunk)
+                .parse_mode(teloxide::types::ParseMode::Html)
+                .disable_web_page_preview(true)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn reply_photo(&self, _incoming: &IncomingMessage, png_bytes: Vec<u8>) -> Result<()> {
+        self.bot.reply_photo(self.msg, InputFile::memory(png_bytes)).await?;
+        Ok(())
+    }
+
+    async fn send_chat_action(&self, _incoming: &IncomingMessage) -> Result<()> {
+        self.bot
+            .send_chat_action(self.msg.chat.id, teloxide::types::ChatAction::Typing)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Splits `text` into chunks no longer than `limit`, breaking only at line
+/// boundaries so HTML tags never get split across messages. A single line
+/// longer than `limit` is not split and becomes its own oversized chunk.
+fn chunk_by_lines(text: &str, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if !current.is_empty() && current.len() + line.len() + 1 > limit {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+async fn cmd_help(bot: Bot, env: Arc<BotEnv>, msg: Message) -> Result<()> {
+    let transport = TelegramTransport { bot: &bot, msg: &msg };
+    cmd_help_impl(&transport, &env, transport.incoming()).await
+}
+
+pub async fn cmd_help_impl(
+    transport: &dyn Transport,
+    env: &Arc<BotEnv>,
+    incoming: IncomingMessage,
+) -> Result<()> {
     let mut text = String::new();
     text.push_str("Available commands:\n\n");
     text.push_str(&commands_help::<crate::modules::basic::Commands>());
@@ -98,10 +233,16 @@ async fn cmd_help(bot: Bot, msg: Message) -> Result<()> {
     text.push_str(&commands_help::<crate::modules::butler::Commands>());
     text.push_str("\nCommands marked with * are available only to residents.");
     // "..., and with ** are available only to bot technicians."
-    bot.reply_message(&msg, text)
-        .parse_mode(teloxide::types::ParseMode::Html)
-        .await?;
-    Ok(())
+
+    let macros = super::macros::list_macros_by_chat_id(env, incoming.chat_id)?;
+    if !macros.is_empty() {
+        text.push_str("\n\nMacros in this chat:\n");
+        for (name, expansion) in macros {
+            let _ = write!(&mut text, "/{name} — {expansion}\n");
+        }
+    }
+
+    transport.reply_html(&incoming, &text).await
 }
 
 fn commands_help<T: BotCommands + BotCommandsExtTrait>() -> String {
@@ -138,10 +279,15 @@ fn commands_help<T: BotCommands + BotCommandsExtTrait>() -> String {
     result
 }
 
-async fn cmd_list_residents(
-    bot: Bot,
-    env: Arc<BotEnv>,
-    msg: Message,
+async fn cmd_list_residents(bot: Bot, env: Arc<BotEnv>, msg: Message) -> Result<()> {
+    let transport = TelegramTransport { bot: &bot, msg: &msg };
+    cmd_list_residents_impl(&transport, &env, transport.incoming()).await
+}
+
+pub async fn cmd_list_residents_impl(
+    transport: &dyn Transport,
+    env: &Arc<BotEnv>,
+    incoming: IncomingMessage,
 ) -> Result<()> {
     let residents: Vec<(DbUserId, Option<models::TgUser>)> =
         schema::residents::table
@@ -161,11 +307,7 @@ async fn cmd_list_residents(
     text.push_str("Residents: ");
     format_users(&mut text, residents.iter().map(|(r, u)| (*r, u)));
     text.push('.');
-    bot.reply_message(&msg, text)
-        .parse_mode(teloxide::types::ParseMode::Html)
-        .disable_web_page_preview(true)
-        .await?;
-    Ok(())
+    transport.reply_html(&incoming, &text).await
 }
 
 async fn cmd_residents_admin_table(
@@ -228,6 +370,7 @@ async fn cmd_show_residents_timeline(bot: Bot, msg: Message) -> Result<()> {
 pub async fn cmd_status_text(
     env: &Arc<BotEnv>,
     state: &Arc<RwLock<State>>,
+    mikrotik_cache: &SharedMikrotikCache,
 ) -> Result<String> {
     let mut text = String::new();
 
@@ -250,6 +393,18 @@ pub async fn cmd_status_text(
         .unwrap();
     }
 
+    let mikrotik_cache = mikrotik_cache.read().await;
+    if mikrotik_cache.degraded() {
+        writeln!(
+            &mut text,
+            "\n⚠️ Mikrotik polling is degraded, presence data may be stale."
+        )
+        .unwrap();
+    }
+    if let Some(last_success) = mikrotik_cache.last_success() {
+        writeln!(&mut text, "(last Mikrotik check: {last_success})").unwrap();
+    }
+
     Ok(text)
 }
 
@@ -258,8 +413,11 @@ async fn cmd_status(
     env: Arc<BotEnv>,
     msg: Message,
     state: Arc<RwLock<State>>,
+    mikrotik_cache: SharedMikrotikCache,
 ) -> Result<()> {
-    // Log on-demand debug info and trigger an immediate Mikrotik check in background
+    // Log on-demand debug info; the Mikrotik lease cache is kept fresh by a
+    // background poller (see `utils::mikrotik::spawn_poller`), so `/status`
+    // never triggers network I/O of its own.
     {
         let who = msg
             .from
@@ -276,107 +434,96 @@ async fn cmd_status(
         );
     }
 
-    {
-        let env = Arc::clone(&env);
-        tokio::spawn(async move {
-            log::debug!("/status: triggering immediate Mikrotik leases fetch");
-            match get_leases(&env.reqwest_client, &env.config.services.mikrotik)
-                .await
-            {
-                Ok(leases) => {
-                    log::info!(
-                        "/status: Mikrotik fetch ok: leases_count={}",
-                        leases.len()
-                    );
-                }
-                Err(e) => {
-                    log::error!("/status: Mikrotik fetch failed: {e}");
-                }
-            }
-        });
-    }
-
-    let text = cmd_status_text(&env, &state).await?;
-
-    bot.reply_message(&msg, text)
-        .parse_mode(teloxide::types::ParseMode::Html)
-        .disable_web_page_preview(true)
-        .await?;
+    let transport = TelegramTransport { bot: &bot, msg: &msg };
+    cmd_status_impl(&transport, &env, &state, &mikrotik_cache, transport.incoming()).await
+}
 
-    Ok(())
+pub async fn cmd_status_impl(
+    transport: &dyn Transport,
+    env: &Arc<BotEnv>,
+    state: &Arc<RwLock<State>>,
+    mikrotik_cache: &SharedMikrotikCache,
+    incoming: IncomingMessage,
+) -> Result<()> {
+    let text = cmd_status_text(env, state, mikrotik_cache).await?;
+    transport.reply_html(&incoming, &text).await
 }
 
 async fn cmd_topics(bot: Bot, env: Arc<BotEnv>, msg: Message) -> Result<()> {
-    let Some(user) = &msg.from else { return Ok(()) };
-
-    let user_chats = schema::tg_users_in_chats::table
-        .filter(schema::tg_users_in_chats::user_id.eq(DbUserId::from(user.id)))
-        .select(schema::tg_users_in_chats::chat_id)
-        .load::<DbChatId>(&mut *env.conn())?;
-
-    if user_chats.is_empty() {
-        bot.reply_message(&msg, "You are not in any tracked chats.").await?;
-        return Ok(());
-    }
+    let transport = TelegramTransport { bot: &bot, msg: &msg };
+    cmd_topics_impl(&transport, &env, transport.incoming()).await
+}
 
-    let topics: Vec<models::TgChatTopic> = schema::tg_chat_topics::table
-        .filter(schema::tg_chat_topics::chat_id.eq_any(user_chats))
-        .select(schema::tg_chat_topics::all_columns)
-        .load(&mut *env.conn())?;
+/// `/topics`: lists the requester's chat topics as plain bulleted text,
+/// shared between Telegram and Matrix through [`Transport`].
+pub async fn cmd_topics_impl(
+    transport: &dyn Transport,
+    env: &Arc<BotEnv>,
+    incoming: IncomingMessage,
+) -> Result<()> {
+    let topics: Vec<(Option<String>, models::TgChatTopic)> = match incoming.user_id {
+        Some(user_id) => {
+            let user_chats = schema::tg_users_in_chats::table
+                .filter(schema::tg_users_in_chats::user_id.eq(user_id))
+                .select(schema::tg_users_in_chats::chat_id)
+                .load::<DbChatId>(&mut *env.conn())?;
+            if user_chats.is_empty() {
+                transport
+                    .reply_html(&incoming, "You are not in any tracked chats.")
+                    .await?;
+                return Ok(());
+            }
+            schema::tg_chat_topics::table
+                .inner_join(
+                    schema::tg_chats::table
+                        .on(schema::tg_chat_topics::chat_id.eq(schema::tg_chats::id)),
+                )
+                .filter(schema::tg_chat_topics::chat_id.eq_any(user_chats))
+                .select((schema::tg_chats::title, schema::tg_chat_topics::all_columns))
+                .load(&mut *env.conn())?
+        }
+        // No resident mapping for this identity (e.g. an unlinked Matrix
+        // account) — fall back to every tracked topic.
+        None => schema::tg_chat_topics::table
+            .inner_join(
+                schema::tg_chats::table
+                    .on(schema::tg_chat_topics::chat_id.eq(schema::tg_chats::id)),
+            )
+            .select((schema::tg_chats::title, schema::tg_chat_topics::all_columns))
+            .load(&mut *env.conn())?,
+    };
 
     if topics.is_empty() {
-        bot.reply_message(&msg, "No topics in your chats.").await?;
+        transport.reply_html(&incoming, "No topics found.").await?;
         return Ok(());
     }
 
-    let topic_emojis = TopicEmojis::fetch(&bot, topics.iter()).await?;
-
-    let mut chats = HashMap::new();
-    for topic in &topics {
-        chats.entry(topic.chat_id).or_insert_with(Vec::new).push(topic);
+    let mut chats: HashMap<Option<String>, Vec<models::TgChatTopic>> = HashMap::new();
+    for (title, topic) in topics {
+        chats.entry(title).or_default().push(topic);
     }
 
     let mut text = String::new();
-    for (chat_id, topics) in chats {
-        let chat: models::TgChat = schema::tg_chats::table
-            .filter(schema::tg_chats::id.eq(chat_id))
-            .first(&mut *env.conn())?;
+    for (title, topics) in chats {
         writeln!(
             &mut text,
             "<b>{}</b>",
-            chat.title.as_ref().map_or(String::new(), |t| html::escape(t))
+            title.as_deref().map_or(String::new(), html::escape)
         )
         .unwrap();
-
         for topic in topics {
-            render_topic_link(&mut text, &topic_emojis, topic);
+            writeln!(
+                &mut text,
+                "• {}",
+                topic
+                    .name
+                    .as_deref()
+                    .map_or_else(|| format!("Topic #{}", topic.topic_id), html::escape)
+            )
+            .unwrap();
         }
         text.push('\n');
     }
 
-    for lines in text.lines().collect_vec().chunks(100) {
-        let text = lines.join("\n");
-        bot.reply_message(&msg, text)
-            .parse_mode(teloxide::types::ParseMode::Html)
-            .disable_web_page_preview(true)
-            .await?;
-    }
-
-    Ok(())
-}
-
-fn render_topic_link(
-    out: &mut String,
-    emojis: &TopicEmojis,
-    topic: &models::TgChatTopic,
-) {
-    write_message_link(out, topic.chat_id, ThreadId::from(topic.topic_id).0);
-    out.push_str(emojis.get(topic));
-    out.push(' ');
-    if let Some(name) = &topic.name {
-        out.push_str(&html::escape(name));
-    } else {
-        write!(out, "Topic #{}", ThreadId::from(topic.topic_id)).unwrap();
-    }
-    out.push_str("</a>\n");
+    transport.reply_html(&incoming, &text).await
 }
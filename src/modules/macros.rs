@@ -0,0 +1,219 @@
+//! User-defined command macros (aliases), stored per-chat in the database.
+//!
+//! Residents can register shortcuts for an existing command invocation,
+//! e.g. `/macro add here status` or `/macro add plants remind 1d water the
+//! plants`. When an incoming command isn't one of the static
+//! [`crate::modules::basic::Commands`] (or those of any other module), the
+//! fallback branch wired up in `basic::command_handler` looks it up here,
+//! substitutes positional arguments, and re-dispatches it as the command it
+//! expands to.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use diesel::prelude::*;
+use teloxide::prelude::*;
+use teloxide::utils::command::BotCommands;
+
+use crate::common::{BotCommandsExtTrait, BotEnv};
+use crate::db::{DbChatId, DbUserId};
+use crate::utils::BotExt;
+use crate::{models, schema};
+
+use super::basic;
+
+pub async fn cmd_macro(bot: Bot, env: Arc<BotEnv>, msg: Message, args: String) -> Result<()> {
+    let chat_id = DbChatId::from(msg.chat.id);
+    let mut words = args.split_whitespace();
+
+    match words.next() {
+        Some("add") => {
+            let Some(name) = words.next() else {
+                bot.reply_message(&msg, "Usage: /macro add <name> <expansion...>").await?;
+                return Ok(());
+            };
+            let expansion = words.collect::<Vec<_>>().join(" ");
+            if expansion.is_empty() {
+                bot.reply_message(&msg, "Usage: /macro add <name> <expansion...>").await?;
+                return Ok(());
+            }
+            if is_known_command(name) {
+                bot.reply_message(
+                    &msg,
+                    format!("/{name} is already a built-in command, pick another name."),
+                )
+                .await?;
+                return Ok(());
+            }
+
+            diesel::insert_into(schema::command_macros::table)
+                .values((
+                    schema::command_macros::chat_id.eq(chat_id),
+                    schema::command_macros::name.eq(name),
+                    schema::command_macros::expansion.eq(&expansion),
+                ))
+                .on_conflict((schema::command_macros::chat_id, schema::command_macros::name))
+                .do_update()
+                .set(schema::command_macros::expansion.eq(&expansion))
+                .execute(&mut *env.conn())?;
+
+            bot.reply_message(&msg, format!("Saved macro /{name} -> {expansion}")).await?;
+        }
+        Some("remove") => {
+            let Some(name) = words.next() else {
+                bot.reply_message(&msg, "Usage: /macro remove <name>").await?;
+                return Ok(());
+            };
+            let deleted = diesel::delete(
+                schema::command_macros::table
+                    .filter(schema::command_macros::chat_id.eq(chat_id))
+                    .filter(schema::command_macros::name.eq(name)),
+            )
+            .execute(&mut *env.conn())?;
+
+            bot.reply_message(
+                &msg,
+                if deleted > 0 {
+                    format!("Removed macro /{name}")
+                } else {
+                    format!("No such macro: /{name}")
+                },
+            )
+            .await?;
+        }
+        _ => {
+            bot.reply_message(&msg, "Usage: /macro add <name> <expansion...>\nor: /macro remove <name>")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the macros registered in `chat_id`, for `/help`.
+pub fn list_macros_by_chat_id(env: &Arc<BotEnv>, chat_id: DbChatId) -> Result<Vec<(String, String)>> {
+    Ok(schema::command_macros::table
+        .filter(schema::command_macros::chat_id.eq(chat_id))
+        .select((schema::command_macros::name, schema::command_macros::expansion))
+        .order(schema::command_macros::name.asc())
+        .load(&mut *env.conn())?)
+}
+
+/// Resolves an incoming text message to a [`basic::Commands`] invocation
+/// via the chat's registered macros. Returns `None` — letting the update
+/// fall through to other handlers — if the message isn't a command, is
+/// already a recognized static command, has no matching macro, or expands
+/// to a resident-gated command and the sender isn't a resident (the same
+/// silent "not a match" `filter_command::<Commands>()` gives a non-resident
+/// who types the built-in command directly).
+pub async fn resolve_macro_command(env: Arc<BotEnv>, msg: Message) -> Option<basic::Commands> {
+    let text = msg.text()?;
+    let rest = text.strip_prefix('/')?;
+    let mut parts = rest.splitn(2, char::is_whitespace);
+    let name = parts.next()?.split('@').next()?;
+    let args = parts.next().unwrap_or_default();
+
+    if name.is_empty() || is_known_command(name) {
+        return None;
+    }
+
+    let chat_id = DbChatId::from(msg.chat.id);
+    let macro_row: models::CommandMacro = schema::command_macros::table
+        .filter(schema::command_macros::chat_id.eq(chat_id))
+        .filter(schema::command_macros::name.eq(name))
+        .first(&mut *env.conn())
+        .optional()
+        .ok()??;
+
+    let expanded = substitute_args(&macro_row.expansion, args);
+    let resolved_name = expanded.split_whitespace().next().unwrap_or_default();
+    if command_requires_resident(resolved_name) {
+        let user = msg.from.as_ref()?;
+        if !is_resident(&env, DbUserId::from(user.id)).unwrap_or(false) {
+            return None;
+        }
+    }
+
+    basic::Commands::parse(&format!("/{expanded}"), "").ok()
+}
+
+/// Whether the `basic::Commands` variant named `name` carries `#[custom(
+/// resident = true)]`, so a macro expanding to it is gated the same as
+/// typing it directly.
+fn command_requires_resident(name: &str) -> bool {
+    std::iter::zip(&basic::Commands::bot_commands(), basic::Commands::COMMAND_RULES)
+        .find(|(cmd, _)| cmd.command.trim_start_matches('/') == name)
+        .is_some_and(|(_, rules)| rules.resident)
+}
+
+fn is_resident(env: &Arc<BotEnv>, user_id: DbUserId) -> Result<bool> {
+    let count: i64 = schema::residents::table
+        .filter(schema::residents::tg_id.eq(user_id))
+        .filter(schema::residents::end_date.is_null())
+        .count()
+        .get_result(&mut *env.conn())?;
+    Ok(count > 0)
+}
+
+/// Replaces `$1`..`$n` with positional arguments and `$@` with the whole
+/// argument string, mirroring shell positional-parameter substitution.
+fn substitute_args(expansion: &str, args: &str) -> String {
+    let positional: Vec<&str> = args.split_whitespace().collect();
+    let mut out = String::with_capacity(expansion.len());
+    let mut chars = expansion.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('@') => {
+                chars.next();
+                out.push_str(args);
+            }
+            Some(d) if d.is_ascii_digit() => {
+                let mut num = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    num.push(d);
+                    chars.next();
+                }
+                if let Some(arg) = num.parse::<usize>().ok().and_then(|i| {
+                    i.checked_sub(1).and_then(|i| positional.get(i))
+                }) {
+                    out.push_str(arg);
+                }
+            }
+            _ => out.push('$'),
+        }
+    }
+    out
+}
+
+fn is_known_command(name: &str) -> bool {
+    fn has<T: BotCommands>(name: &str) -> bool {
+        T::bot_commands().iter().any(|c| c.command.trim_start_matches('/') == name)
+    }
+
+    has::<basic::Commands>(name)
+        || has::<crate::modules::needs::Commands>(name)
+        || has::<crate::modules::userctl::Commands>(name)
+        || has::<crate::modules::camera::Commands>(name)
+        || has::<crate::modules::ldap::Commands>(name)
+        || has::<crate::modules::butler::Commands>(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_positional_and_rest() {
+        assert_eq!(substitute_args("remind $1 water $2", "1d the plants"), "remind 1d water the");
+        assert_eq!(substitute_args("remind $@", "1d water the plants"), "remind 1d water the plants");
+    }
+
+    #[test]
+    fn missing_positional_is_empty() {
+        assert_eq!(substitute_args("remind $1 $2", "1d"), "remind 1d ");
+    }
+}
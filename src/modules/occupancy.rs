@@ -0,0 +1,153 @@
+//! Persistent occupancy history and the `/occupancy` command.
+//!
+//! `mac_monitoring::State` only ever holds the *current* set of active
+//! users; this module snapshots it to the `occupancy_log` table on every
+//! Mikrotik poll so residents can later ask "when is anyone usually here".
+
+use std::collections::HashSet;
+use std::io::Write as _;
+use std::process::Command;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{Duration, Timelike, Utc};
+use diesel::prelude::*;
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, UserId};
+
+use crate::common::BotEnv;
+use crate::db::DbUserId;
+use crate::utils::BotExt;
+use crate::schema;
+
+/// How long raw, per-poll rows are kept before being rolled up into
+/// `occupancy_hourly` and deleted, to keep `occupancy_log` bounded.
+const RAW_RETENTION: Duration = Duration::weeks(4);
+
+/// Snapshots the current active-user set and head-count. Called from the
+/// Mikrotik poll loop after each successful probe, with the active-user set
+/// read from `mac_monitoring::State`.
+pub fn log_snapshot(
+    env: &Arc<BotEnv>,
+    active_users: &HashSet<UserId>,
+    head_count: usize,
+) -> Result<()> {
+    let mut conn = env.conn();
+    conn.transaction(|conn| -> Result<()> {
+        let taken_at = Utc::now().naive_utc();
+        diesel::insert_into(schema::occupancy_log::table)
+            .values((
+                schema::occupancy_log::taken_at.eq(taken_at),
+                schema::occupancy_log::head_count.eq(head_count as i32),
+            ))
+            .execute(conn)?;
+        let occupancy_log_id: i32 = schema::occupancy_log::table
+            .select(schema::occupancy_log::id)
+            .order(schema::occupancy_log::id.desc())
+            .first(conn)?;
+
+        let rows: Vec<_> = active_users
+            .iter()
+            .map(|&id| {
+                (
+                    schema::occupancy_log_users::occupancy_log_id.eq(occupancy_log_id),
+                    schema::occupancy_log_users::user_id.eq(DbUserId::from(id)),
+                )
+            })
+            .collect();
+        if !rows.is_empty() {
+            diesel::insert_into(schema::occupancy_log_users::table)
+                .values(rows)
+                .execute(conn)?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Rolls raw rows older than [`RAW_RETENTION`] up into `occupancy_hourly`
+/// buckets, then deletes them. Intended to be run periodically (e.g.
+/// alongside the Mikrotik poll loop).
+pub fn rollup_old_entries(env: &Arc<BotEnv>) -> Result<()> {
+    let cutoff = (Utc::now() - RAW_RETENTION).naive_utc();
+    let mut conn = env.conn();
+
+    let stale: Vec<(i32, chrono::NaiveDateTime, i32)> = schema::occupancy_log::table
+        .filter(schema::occupancy_log::taken_at.lt(cutoff))
+        .select((
+            schema::occupancy_log::id,
+            schema::occupancy_log::taken_at,
+            schema::occupancy_log::head_count,
+        ))
+        .load(&mut *conn)?;
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    use std::collections::BTreeMap;
+    let mut buckets: BTreeMap<chrono::NaiveDateTime, (i32, HashSet<DbUserId>)> = BTreeMap::new();
+    for (id, taken_at, head_count) in &stale {
+        let hour_start = taken_at.date().and_hms_opt(taken_at.time().hour(), 0, 0).unwrap();
+        let users: Vec<DbUserId> = schema::occupancy_log_users::table
+            .filter(schema::occupancy_log_users::occupancy_log_id.eq(id))
+            .select(schema::occupancy_log_users::user_id)
+            .load(&mut *conn)?;
+        let bucket = buckets.entry(hour_start).or_insert((0, HashSet::new()));
+        bucket.0 = bucket.0.max(*head_count);
+        bucket.1.extend(users);
+    }
+
+    for (hour_start, (max_head_count, users)) in buckets {
+        diesel::insert_into(schema::occupancy_hourly::table)
+            .values((
+                schema::occupancy_hourly::hour_start.eq(hour_start),
+                schema::occupancy_hourly::max_head_count.eq(max_head_count),
+                schema::occupancy_hourly::distinct_user_count.eq(users.len() as i32),
+            ))
+            .on_conflict(schema::occupancy_hourly::hour_start)
+            .do_update()
+            .set((
+                schema::occupancy_hourly::max_head_count.eq(max_head_count),
+                schema::occupancy_hourly::distinct_user_count.eq(users.len() as i32),
+            ))
+            .execute(&mut *conn)?;
+    }
+
+    let stale_ids: Vec<i32> = stale.iter().map(|(id, ..)| *id).collect();
+    diesel::delete(
+        schema::occupancy_log_users::table
+            .filter(schema::occupancy_log_users::occupancy_log_id.eq_any(&stale_ids)),
+    )
+    .execute(&mut *conn)?;
+    diesel::delete(schema::occupancy_log::table.filter(schema::occupancy_log::id.eq_any(&stale_ids)))
+        .execute(&mut *conn)?;
+
+    Ok(())
+}
+
+/// `/occupancy`: renders a weekly day/hour presence heatmap as a PNG, via
+/// the same SVG -> `convert` pipeline `cmd_show_residents_timeline` uses.
+pub async fn cmd_occupancy(bot: Bot, msg: Message) -> Result<()> {
+    let svg = Command::new("f0-occupancy-heatmap")
+        .arg("-sqlite")
+        .arg(crate::DB_FILENAME)
+        .output()?;
+    if !svg.status.success() || !svg.stdout.starts_with(b"<svg") {
+        bot.reply_message(&msg, "Failed to generate occupancy heatmap (svg).").await?;
+        return Ok(());
+    }
+    let mut png = Command::new("convert")
+        .arg("svg:-")
+        .arg("png:-")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    png.stdin.take().unwrap().write_all(&svg.stdout)?;
+    let png = png.wait_with_output()?;
+    if !png.status.success() || !png.stdout.starts_with(b"\x89PNG") {
+        bot.reply_message(&msg, "Failed to generate occupancy heatmap (png).").await?;
+        return Ok(());
+    }
+    bot.reply_photo(&msg, InputFile::memory(png.stdout)).await?;
+    Ok(())
+}
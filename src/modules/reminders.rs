@@ -0,0 +1,175 @@
+//! Natural-language `/remind` command: one-off and recurring reminders.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use teloxide::prelude::*;
+use teloxide::types::ThreadId;
+
+use crate::common::BotEnv;
+use crate::db::{DbChatId, DbUserId};
+use crate::utils::interval::{parse_leading_schedule, ReminderSchedule};
+use crate::utils::BotExt;
+use crate::{models, schema};
+
+/// How far into the future a one-off reminder may be scheduled.
+const MAX_HORIZON: chrono::Duration = chrono::Duration::weeks(52);
+
+/// How long a poll of an empty reminders table sleeps for before checking
+/// again.
+const IDLE_POLL: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub async fn cmd_remind(
+    bot: Bot,
+    env: Arc<BotEnv>,
+    msg: Message,
+    text: String,
+) -> Result<()> {
+    let Some(user) = &msg.from else { return Ok(()) };
+
+    if text.trim().is_empty() {
+        bot.reply_message(
+            &msg,
+            "Usage: /remind 2h30m water the plants\nor: /remind every tuesday 07:00 clean the lab",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let (schedule, reminder_text) = match parse_leading_schedule(&text) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            bot.reply_message(&msg, format!("Could not parse that reminder: {e}")).await?;
+            return Ok(());
+        }
+    };
+    if reminder_text.trim().is_empty() {
+        bot.reply_message(&msg, "Please include a reminder text after the time.").await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let (fire_at, recurrence) = match schedule {
+        ReminderSchedule::Once(duration) => {
+            if duration <= chrono::Duration::zero() {
+                bot.reply_message(&msg, "That's already in the past.").await?;
+                return Ok(());
+            }
+            if duration > MAX_HORIZON {
+                bot.reply_message(&msg, "That's too far in the future.").await?;
+                return Ok(());
+            }
+            (now + duration, None)
+        }
+        ReminderSchedule::Cron(expr) => {
+            let next = next_cron_fire_at(&expr, now)
+                .context("failed to compute the next occurrence")?;
+            (next, Some(expr))
+        }
+    };
+
+    diesel::insert_into(schema::reminders::table)
+        .values((
+            schema::reminders::chat_id.eq(DbChatId::from(msg.chat.id)),
+            schema::reminders::thread_id.eq(msg.thread_id.map(|t| t.0 .0 as i64)),
+            schema::reminders::user_id.eq(DbUserId::from(user.id)),
+            schema::reminders::text.eq(&reminder_text),
+            schema::reminders::fire_at.eq(fire_at.naive_utc()),
+            schema::reminders::recurrence.eq(&recurrence),
+            schema::reminders::created_at.eq(now.naive_utc()),
+        ))
+        .execute(&mut *env.conn())?;
+
+    bot.reply_message(&msg, format!("Okay, I'll remind you: {reminder_text}")).await?;
+    Ok(())
+}
+
+/// Background task: sleeps until the nearest reminder's `fire_at`, posts it
+/// as a new message in the originating chat and thread, then either deletes the row or
+/// reschedules it from its cron recurrence.
+pub async fn dispatcher(bot: Bot, env: Arc<BotEnv>) {
+    loop {
+        let next: Option<models::Reminder> = schema::reminders::table
+            .order(schema::reminders::fire_at.asc())
+            .first(&mut *env.conn())
+            .optional()
+            .unwrap_or_default();
+
+        let Some(reminder) = next else {
+            tokio::time::sleep(IDLE_POLL).await;
+            continue;
+        };
+
+        let now = Utc::now().naive_utc();
+        if reminder.fire_at > now {
+            let sleep_for = (reminder.fire_at - now).to_std().unwrap_or(IDLE_POLL);
+            tokio::time::sleep(sleep_for.min(IDLE_POLL)).await;
+            continue;
+        }
+
+        if let Err(e) = fire(&bot, &reminder).await {
+            log::error!("reminders: failed to send reminder {}: {e}", reminder.id);
+        }
+
+        match &reminder.recurrence {
+            Some(expr) => match next_cron_fire_at(expr, Utc::now()) {
+                Ok(next_fire_at) => {
+                    if let Err(e) = diesel::update(schema::reminders::table.find(reminder.id))
+                        .set(schema::reminders::fire_at.eq(next_fire_at.naive_utc()))
+                        .execute(&mut *env.conn())
+                    {
+                        log::error!("reminders: failed to reschedule {}: {e}", reminder.id);
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "reminders: bad recurrence {expr:?} for reminder {}, dropping it: {e}",
+                        reminder.id
+                    );
+                    delete(&env, reminder.id);
+                }
+            },
+            None => delete(&env, reminder.id),
+        }
+    }
+}
+
+fn delete(env: &Arc<BotEnv>, id: i32) {
+    if let Err(e) =
+        diesel::delete(schema::reminders::table.find(id)).execute(&mut *env.conn())
+    {
+        log::error!("reminders: failed to delete fired reminder {id}: {e}");
+    }
+}
+
+async fn fire(bot: &Bot, reminder: &models::Reminder) -> Result<()> {
+    let chat_id = reminder.chat_id.into();
+    let mut request = bot.send_message(chat_id, &reminder.text);
+    if let Some(thread_id) = reminder.thread_id {
+        request = request.message_thread_id(ThreadId::from(thread_id as i32));
+    }
+    request.await?;
+    Ok(())
+}
+
+/// Resolves the next UTC fire time for a 7-field cron expression, evaluated
+/// in the space's local timezone — the same machinery `VortexOfDoom`
+/// already uses to dispatch its weekly schedule.
+fn next_cron_fire_at(
+    expr: &str,
+    now: chrono::DateTime<Utc>,
+) -> Result<chrono::DateTime<Utc>> {
+    use chrono_tz::Europe::Chisinau as SPACE_TZ;
+
+    let schedule = cron::Schedule::from_str(expr)
+        .with_context(|| format!("invalid cron expression {expr:?}"))?;
+    let now_local = now.with_timezone(&SPACE_TZ);
+    let next_local = schedule
+        .after(&now_local)
+        .next()
+        .context("cron schedule never fires")?;
+    Ok(next_local.with_timezone(&Utc))
+}
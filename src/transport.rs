@@ -0,0 +1,33 @@
+//! Transport abstraction for the read-only commands residents rely on most
+//! (`/help`, `/residents`, `/status`, `/topics`), so they aren't hard-wired
+//! to Telegram. `modules::basic` implements [`Transport`] for Telegram;
+//! [`crate::matrix`] implements it for the space's Matrix homeserver.
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::db::{DbChatId, DbUserId};
+
+/// Who asked and from where, reduced to what the shared commands need.
+#[derive(Debug, Clone, Copy)]
+pub struct IncomingMessage {
+    pub chat_id: DbChatId,
+    pub user_id: Option<DbUserId>,
+}
+
+/// A chat backend the shared read commands can reply through.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Replies to `incoming` with HTML-formatted text (both Telegram and
+    /// Matrix accept a basic HTML subset natively). Implementations are
+    /// responsible for splitting `html` if the backend caps message size.
+    async fn reply_html(&self, incoming: &IncomingMessage, html: &str) -> Result<()>;
+
+    /// Replies to `incoming` with a PNG image, e.g. the heatmaps/timelines
+    /// rendered by `f0-occupancy-heatmap`/`f0-residents-timeline`.
+    async fn reply_photo(&self, incoming: &IncomingMessage, png_bytes: Vec<u8>) -> Result<()>;
+
+    /// Signals that a reply is being worked on (Telegram's "typing..."
+    /// indicator, Matrix's typing notice).
+    async fn send_chat_action(&self, incoming: &IncomingMessage) -> Result<()>;
+}
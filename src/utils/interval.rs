@@ -0,0 +1,134 @@
+//! Parsing of the human-written time specs accepted by `/remind`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Duration, NaiveTime, Timelike, Weekday};
+
+/// The two shapes a `/remind` time spec can take once parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReminderSchedule {
+    /// Fire once, after this much time has passed.
+    Once(Duration),
+    /// Fire repeatedly according to a 7-field cron expression, in the same
+    /// format as `VortexOfDoom::schedule` (e.g. `"0 0 7 * * 2 *"`).
+    Cron(String),
+}
+
+/// Parses the leading time spec off `input` and returns it together with
+/// the remaining free text, e.g. `"2h30m water the plants"` ->
+/// `(Once(9000s), "water the plants")`, or `"every tuesday 07:00 clean the
+/// lab"` -> `(Cron("0 0 7 * * 2 *"), "clean the lab")`.
+pub fn parse_leading_schedule(input: &str) -> Result<(ReminderSchedule, String)> {
+    let input = input.trim();
+    if let Some(rest) = input.strip_prefix("every ") {
+        return parse_recurrence(rest);
+    }
+    if let Some((duration, rest)) = parse_relative_duration(input) {
+        return Ok((ReminderSchedule::Once(duration), rest));
+    }
+    bail!("could not find a time spec at the start of {input:?}");
+}
+
+/// Tokenizes a leading run of `<number><unit>` chunks, where unit is one of
+/// `s`, `m`, `h`, `d`, `w`, and sums them into a single duration, e.g.
+/// `"2h30m water"` -> `(9000s, "water")`. Returns `None` if `input` doesn't
+/// start with at least one such chunk.
+fn parse_relative_duration(input: &str) -> Option<(Duration, String)> {
+    let bytes = input.as_bytes();
+    let mut pos = 0;
+    let mut total = Duration::zero();
+    let mut saw_chunk = false;
+
+    loop {
+        let digits_start = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos == digits_start {
+            break;
+        }
+        // A number without a valid unit isn't part of the spec; leave it in
+        // the remaining text.
+        let unit_duration = match bytes.get(pos) {
+            Some(b's') => Duration::seconds(1),
+            Some(b'm') => Duration::minutes(1),
+            Some(b'h') => Duration::hours(1),
+            Some(b'd') => Duration::days(1),
+            Some(b'w') => Duration::weeks(1),
+            _ => {
+                pos = digits_start;
+                break;
+            }
+        };
+        let Ok(count) = input[digits_start..pos].parse::<i32>() else {
+            pos = digits_start;
+            break;
+        };
+        total += unit_duration * count;
+        pos += 1;
+        saw_chunk = true;
+    }
+
+    if !saw_chunk {
+        return None;
+    }
+    Some((total, input[pos..].trim_start().to_string()))
+}
+
+fn parse_recurrence(rest: &str) -> Result<(ReminderSchedule, String)> {
+    let mut parts = rest.splitn(3, char::is_whitespace);
+    let weekday_str = parts.next().filter(|s| !s.is_empty()).context(
+        "expected \"every <weekday> HH:MM ...\"",
+    )?;
+    let time_str = parts
+        .next()
+        .context("expected \"every <weekday> HH:MM ...\"")?;
+    let text = parts.next().unwrap_or_default().to_string();
+
+    let weekday: Weekday = weekday_str
+        .parse()
+        .map_err(|_| anyhow!("unknown weekday {weekday_str:?}"))?;
+    let time = NaiveTime::parse_from_str(time_str, "%H:%M")
+        .with_context(|| format!("invalid time {time_str:?}, expected HH:MM"))?;
+
+    // Cron's day-of-week matches `Weekday::num_days_from_sunday`, the same
+    // convention `default_vortex_of_doom_schedule` already relies on.
+    let cron = format!(
+        "0 {} {} * * {} *",
+        time.minute(),
+        time.hour(),
+        weekday.num_days_from_sunday()
+    );
+    Ok((ReminderSchedule::Cron(cron), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_duration() {
+        let (schedule, rest) = parse_leading_schedule("2h30m water the plants").unwrap();
+        assert_eq!(schedule, ReminderSchedule::Once(Duration::seconds(9000)));
+        assert_eq!(rest, "water the plants");
+    }
+
+    #[test]
+    fn single_unit() {
+        let (schedule, rest) = parse_leading_schedule("45m stretch").unwrap();
+        assert_eq!(schedule, ReminderSchedule::Once(Duration::minutes(45)));
+        assert_eq!(rest, "stretch");
+    }
+
+    #[test]
+    fn recurrence() {
+        let (schedule, rest) =
+            parse_leading_schedule("every tuesday 07:00 clean the lab").unwrap();
+        assert_eq!(schedule, ReminderSchedule::Cron("0 0 7 * * 2 *".to_string()));
+        assert_eq!(rest, "clean the lab");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_leading_schedule("water the plants").is_err());
+    }
+}
@@ -1,8 +1,16 @@
+use std::sync::Arc;
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use tokio::sync::RwLock;
+
+use crate::common::BotEnv;
 use crate::config::Mikrotik;
+use crate::modules::mac_monitoring::State;
+use crate::modules::occupancy;
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct Lease {
     pub mac_address: String,
@@ -81,6 +89,149 @@ pub async fn get_leases(
         Err(_e_https) => attempt(reqwest_client, conf, "http").await,
     };
 
-    crate::metrics::update_service("mikrotik", leases.is_ok());
     leases
 }
+
+/// Consecutive failures required to trip the circuit breaker.
+const TRIP_THRESHOLD: u32 = 5;
+/// Polling interval while the breaker is closed and probes are succeeding.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// Ceiling for the exponential backoff applied between failed probes.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// How often to roll stale `occupancy_log` rows up into `occupancy_hourly`.
+const ROLLUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breaker {
+    Closed,
+    Open,
+}
+
+/// The last-known-good lease snapshot plus health bookkeeping, shared
+/// behind an `RwLock` so `/status` reads it instead of triggering network
+/// I/O of its own.
+#[derive(Debug, Default)]
+pub struct MikrotikCache {
+    leases: Vec<Lease>,
+    last_success: Option<DateTime<Utc>>,
+    consecutive_errors: u32,
+    breaker: Option<Breaker>,
+}
+
+impl MikrotikCache {
+    /// Last-known-good leases. Empty until the first successful probe.
+    pub fn leases(&self) -> &[Lease] {
+        &self.leases
+    }
+
+    /// When the last successful probe completed, for a freshness timestamp.
+    pub fn last_success(&self) -> Option<DateTime<Utc>> {
+        self.last_success
+    }
+
+    /// Whether the circuit breaker is open, i.e. presence data may be
+    /// stale because the router has stopped responding.
+    pub fn degraded(&self) -> bool {
+        self.breaker == Some(Breaker::Open)
+    }
+}
+
+pub type SharedMikrotikCache = Arc<RwLock<MikrotikCache>>;
+
+/// Spawns the long-lived poller for one configured Mikrotik router and
+/// returns the shared cache it maintains. Replaces the old pattern of
+/// calling [`get_leases`] fresh on every `/status`: one task owns the lease
+/// cache, applies exponential backoff with jitter after failures, and
+/// trips a circuit breaker after [`TRIP_THRESHOLD`] consecutive errors so
+/// `/status` degrades gracefully instead of hammering the router.
+///
+/// Each successful probe also snapshots presence into `occupancy_log` (see
+/// [`occupancy::log_snapshot`]), using `mac_monitoring_state` for the
+/// active-user set and the poll's own lease count as the head-count — the
+/// lease payload this poller fetches otherwise had no reader at all. A
+/// second background task rolls stale `occupancy_log` rows up into
+/// `occupancy_hourly` on [`ROLLUP_INTERVAL`] (see
+/// [`occupancy::rollup_old_entries`]).
+pub fn spawn_poller(
+    reqwest_client: reqwest::Client,
+    conf: Mikrotik,
+    env: Arc<BotEnv>,
+    mac_monitoring_state: Arc<RwLock<State>>,
+) -> SharedMikrotikCache {
+    let cache: SharedMikrotikCache = Arc::new(RwLock::new(MikrotikCache::default()));
+    let shared = Arc::clone(&cache);
+
+    tokio::spawn({
+        let env = Arc::clone(&env);
+        async move {
+            loop {
+                tokio::time::sleep(ROLLUP_INTERVAL).await;
+                if let Err(e) = occupancy::rollup_old_entries(&env) {
+                    log::error!("occupancy: failed to roll up old entries: {e}");
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut backoff = POLL_INTERVAL;
+        loop {
+            match get_leases(&reqwest_client, &conf).await {
+                Ok(leases) => {
+                    let mut state = shared.write().await;
+                    let was_degraded = state.degraded();
+                    state.leases = leases;
+                    state.last_success = Some(Utc::now());
+                    state.consecutive_errors = 0;
+                    state.breaker = Some(Breaker::Closed);
+                    drop(state);
+
+                    if was_degraded {
+                        log::info!(
+                            "mikrotik poller: circuit breaker closed again after a successful probe"
+                        );
+                    }
+                    crate::metrics::update_service("mikrotik", true);
+
+                    let head_count = shared.read().await.leases().len();
+                    if let Some(active_users) = mac_monitoring_state.read().await.active_users() {
+                        if let Err(e) = occupancy::log_snapshot(&env, &active_users, head_count) {
+                            log::error!("occupancy: failed to log snapshot: {e}");
+                        }
+                    }
+
+                    backoff = POLL_INTERVAL;
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Err(e) => {
+                    let mut state = shared.write().await;
+                    state.consecutive_errors += 1;
+                    let consecutive_errors = state.consecutive_errors;
+                    let just_tripped =
+                        consecutive_errors == TRIP_THRESHOLD && !state.degraded();
+                    if consecutive_errors >= TRIP_THRESHOLD {
+                        state.breaker = Some(Breaker::Open);
+                    }
+                    drop(state);
+
+                    if just_tripped {
+                        log::warn!(
+                            "mikrotik poller: {consecutive_errors} consecutive failures, circuit breaker is now open: {e}"
+                        );
+                    } else {
+                        log::warn!(
+                            "mikrotik poller: probe failed ({consecutive_errors}/{TRIP_THRESHOLD}): {e}"
+                        );
+                    }
+                    crate::metrics::update_service("mikrotik", false);
+
+                    let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..250));
+                    tokio::time::sleep((backoff + jitter).min(MAX_BACKOFF)).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    });
+
+    cache
+}